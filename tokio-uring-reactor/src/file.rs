@@ -1,6 +1,11 @@
+use std::fmt;
 use std::fs;
+use std::io;
+use std::io::SeekFrom;
 use std::os::unix::io::{RawFd, AsRawFd, IntoRawFd};
 
+use crate::reactor::{Handle, AsyncRead, AsyncWrite};
+
 #[derive(Debug)]
 pub struct File(fs::File);
 
@@ -24,3 +29,148 @@ impl IntoRawFd for File {
 
 impl crate::io::FileRead for File {}
 impl crate::io::FileWrite for File {}
+
+enum UringFileState {
+	Idle(File),
+	Reading(AsyncRead<Vec<u8>, File>),
+	Writing(AsyncWrite<Vec<u8>, File>),
+	// a previous op consumed the file and failed to hand it back (reactor
+	// gone); there's nothing left to drive further ops with
+	Closed,
+}
+
+/// Adapts a `Handle`-driven `File` to the standard `futures-io`
+/// `AsyncRead`/`AsyncWrite`/`AsyncSeek` traits, so it composes with
+/// `AsyncReadExt::read_to_end`, `copy`, buffered readers, and the rest of
+/// the futures ecosystem instead of only the one-shot `AsyncRead`/
+/// `AsyncWrite` futures in `reactor`.
+pub struct UringFile {
+	handle: Handle,
+	cursor: u64,
+	// reused scratch buffer for in-flight reads/writes, resized to fit
+	// each call instead of allocating fresh every time
+	buf: Vec<u8>,
+	state: UringFileState,
+}
+
+impl UringFile {
+	pub fn new(handle: Handle, file: File) -> Self {
+		UringFile {
+			handle,
+			cursor: 0,
+			buf: Vec::new(),
+			state: UringFileState::Idle(file),
+		}
+	}
+}
+
+impl fmt::Debug for UringFile {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "UringFile {{ cursor: {}, .. }}", self.cursor)
+	}
+}
+
+impl futures_io::AsyncRead for UringFile {
+	fn poll_read(&mut self, buf: &mut [u8]) -> futures::Poll<usize, io::Error> {
+		let mut fut = match std::mem::replace(&mut self.state, UringFileState::Closed) {
+			UringFileState::Idle(file) => {
+				let mut scratch = std::mem::replace(&mut self.buf, Vec::new());
+				scratch.clear();
+				scratch.resize(buf.len(), 0u8);
+				self.handle.async_read(file, self.cursor, scratch)
+			},
+			UringFileState::Reading(fut) => fut,
+			UringFileState::Writing(fut) => {
+				self.state = UringFileState::Writing(fut);
+				return Err(io::Error::new(io::ErrorKind::Other, "a write is already in progress"));
+			},
+			UringFileState::Closed => return Err(io::Error::new(io::ErrorKind::Other, "uring file closed")),
+		};
+
+		match fut.poll() {
+			Ok(futures::Async::NotReady) => {
+				self.state = UringFileState::Reading(fut);
+				Ok(futures::Async::NotReady)
+			},
+			Ok(futures::Async::Ready((n, scratch, file))) => {
+				buf[..n].copy_from_slice(&scratch[..n]);
+				self.cursor += n as u64;
+				self.buf = scratch;
+				self.state = UringFileState::Idle(file);
+				Ok(futures::Async::Ready(n))
+			},
+			Err((e, scratch, file)) => {
+				self.buf = scratch;
+				self.state = UringFileState::Idle(file);
+				Err(e)
+			},
+		}
+	}
+}
+
+impl futures_io::AsyncWrite for UringFile {
+	fn poll_write(&mut self, buf: &[u8]) -> futures::Poll<usize, io::Error> {
+		let mut fut = match std::mem::replace(&mut self.state, UringFileState::Closed) {
+			UringFileState::Idle(file) => {
+				let mut scratch = std::mem::replace(&mut self.buf, Vec::new());
+				scratch.clear();
+				scratch.extend_from_slice(buf);
+				match self.handle.async_write(file, self.cursor, scratch) {
+					Ok(fut) => fut,
+					Err(e) => return Err(e),
+				}
+			},
+			UringFileState::Writing(fut) => fut,
+			UringFileState::Reading(fut) => {
+				self.state = UringFileState::Reading(fut);
+				return Err(io::Error::new(io::ErrorKind::Other, "a read is already in progress"));
+			},
+			UringFileState::Closed => return Err(io::Error::new(io::ErrorKind::Other, "uring file closed")),
+		};
+
+		match fut.poll() {
+			Ok(futures::Async::NotReady) => {
+				self.state = UringFileState::Writing(fut);
+				Ok(futures::Async::NotReady)
+			},
+			Ok(futures::Async::Ready((n, scratch, file))) => {
+				self.cursor += n as u64;
+				self.buf = scratch;
+				self.state = UringFileState::Idle(file);
+				Ok(futures::Async::Ready(n))
+			},
+			Err((e, scratch, file)) => {
+				self.buf = scratch;
+				self.state = UringFileState::Idle(file);
+				Err(e)
+			},
+		}
+	}
+
+	fn poll_flush(&mut self) -> futures::Poll<(), io::Error> {
+		// by the time poll_write reports completion the kernel has already
+		// taken the data (its writev completed); no buffering here to flush.
+		// This doesn't fsync, so the data isn't guaranteed durable yet
+		Ok(futures::Async::Ready(()))
+	}
+
+	fn shutdown(&mut self) -> futures::Poll<(), io::Error> {
+		Ok(futures::Async::Ready(()))
+	}
+}
+
+impl futures_io::AsyncSeek for UringFile {
+	fn poll_seek(&mut self, pos: SeekFrom) -> futures::Poll<u64, io::Error> {
+		// the kernel offset is per-op via the SQE, so "seeking" is just
+		// moving the cursor we pass to the next read/write
+		match pos {
+			SeekFrom::Start(n) => self.cursor = n,
+			SeekFrom::Current(delta) if delta >= 0 => self.cursor = self.cursor.saturating_add(delta as u64),
+			SeekFrom::Current(delta) => self.cursor = self.cursor.saturating_sub((-delta) as u64),
+			SeekFrom::End(_) => {
+				return Err(io::Error::new(io::ErrorKind::Other, "seeking from the end is not supported"));
+			},
+		}
+		Ok(futures::Async::Ready(self.cursor))
+	}
+}